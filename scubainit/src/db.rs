@@ -0,0 +1,528 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::entfiles::{EntFileReader, Entry, ReadEntryError};
+use crate::groups::{GroupEntry, GroupFileWriter};
+use crate::password::Password;
+use crate::passwd::{Gecos, PasswdEntry, PasswdFileWriter};
+use crate::shadow::{ShadowEntry, ShadowFileWriter};
+use crate::util::open_read_append;
+
+/// The login shell assigned to a [`UserSpec`]-provisioned account when none
+/// is given explicitly.
+pub const DEFAULT_SHELL: &str = "/bin/bash";
+
+////////////////////////////////////////////////////////////////////////////////
+// DbError
+
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error("error reading entry file")]
+    Read(#[from] ReadEntryError),
+
+    #[error("error writing entry file")]
+    Io(#[from] std::io::Error),
+
+    #[error("user '{0}' has a shadow entry, so its passwd field must be \"x\"")]
+    NotShadowed(String),
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Files
+
+/// Paths to the three NSS "ent" files that together make up a user database.
+pub struct Files {
+    pub passwd: PathBuf,
+    pub group: PathBuf,
+    pub shadow: PathBuf,
+}
+
+impl Files {
+    pub fn new<P: Into<PathBuf>>(passwd: P, group: P, shadow: P) -> Files {
+        Files {
+            passwd: passwd.into(),
+            group: group.into(),
+            shadow: shadow.into(),
+        }
+    }
+
+    fn read_all<T: Entry>(path: &Path) -> Result<Vec<T>, DbError> {
+        let file = File::open(path)?;
+        EntFileReader::<T>::new(file)
+            .collect::<Result<Vec<T>, ReadEntryError>>()
+            .map_err(DbError::from)
+    }
+}
+
+impl Default for Files {
+    /// The standard system locations: `/etc/passwd`, `/etc/group`, `/etc/shadow`.
+    fn default() -> Files {
+        Files::new("/etc/passwd", "/etc/group", "/etc/shadow")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// UserRecord
+
+/// A user's passwd row joined with its shadow row (matched by name) and
+/// every group that lists the user as a member.
+#[derive(Debug, Eq, PartialEq)]
+pub struct UserRecord {
+    pub passwd: PasswdEntry,
+    pub shadow: Option<ShadowEntry>,
+    pub groups: Vec<GroupEntry>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// UserSpec
+
+/// Builds a coordinated [`UserRecord`] for a new account, filling in
+/// sensible defaults so callers don't have to hand-synchronize the
+/// uid/gid/name fields across separate `PasswdEntry`/`GroupEntry`/
+/// `ShadowEntry` structs.
+///
+/// The produced account's password is locked (see [`Password::Locked`])
+/// until explicitly set.
+pub struct UserSpec {
+    name: String,
+    uid: u32,
+    gid: u32,
+    group_name: String,
+    home_dir: String,
+    shell: Option<String>,
+    gecos: Option<String>,
+    supplementary_groups: Vec<(String, u32)>,
+    password: PasswordSpec,
+}
+
+/// The account's initial login password, as requested via [`UserSpec`].
+#[derive(Default)]
+enum PasswordSpec {
+    /// No login password; the account is locked (see [`Password::Locked`]).
+    #[default]
+    Locked,
+    /// A precomputed crypt(3) hash, stored verbatim.
+    Hash(String),
+    /// A plaintext password, hashed with SHA-512-crypt on [`UserSpec::build`].
+    PlainText(String),
+}
+
+impl UserSpec {
+    /// Starts building a spec for an account named `name`, owned by
+    /// `uid`/`gid`, whose primary group is `group_name`, with home
+    /// directory `home_dir`.
+    pub fn builder(
+        name: impl Into<String>,
+        uid: u32,
+        gid: u32,
+        group_name: impl Into<String>,
+        home_dir: impl Into<String>,
+    ) -> UserSpec {
+        UserSpec {
+            name: name.into(),
+            uid,
+            gid,
+            group_name: group_name.into(),
+            home_dir: home_dir.into(),
+            shell: None,
+            gecos: None,
+            supplementary_groups: Vec::new(),
+            password: PasswordSpec::default(),
+        }
+    }
+
+    /// Overrides the default login shell.
+    pub fn shell(mut self, shell: impl Into<String>) -> UserSpec {
+        self.shell = Some(shell.into());
+        self
+    }
+
+    /// Sets the GECOS full-name subfield. Defaults to the account name.
+    pub fn gecos(mut self, full_name: impl Into<String>) -> UserSpec {
+        self.gecos = Some(full_name.into());
+        self
+    }
+
+    /// Adds a supplementary group the account should be a member of,
+    /// creating it in `/etc/group` (with this given gid) if it doesn't
+    /// already exist.
+    pub fn supplementary_group(mut self, group_name: impl Into<String>, gid: u32) -> UserSpec {
+        self.supplementary_groups.push((group_name.into(), gid));
+        self
+    }
+
+    /// Sets the account's login password to a precomputed crypt(3) hash
+    /// (e.g. `$6$...`), stored verbatim. Replaces any password set by an
+    /// earlier [`UserSpec::password`] call; a later one replaces this.
+    pub fn password_hash(mut self, hash: impl Into<String>) -> UserSpec {
+        self.password = PasswordSpec::Hash(hash.into());
+        self
+    }
+
+    /// Sets the account's login password to `plaintext`, hashed with
+    /// SHA-512-crypt (`$6$`) using a freshly generated salt on
+    /// [`UserSpec::build`].
+    pub fn password(mut self, plaintext: impl Into<String>) -> UserSpec {
+        self.password = PasswordSpec::PlainText(plaintext.into());
+        self
+    }
+
+    /// Finishes the spec, producing the passwd/shadow/group trio.
+    ///
+    /// `today` is the current day count since the epoch (see
+    /// [`crate::util::today_days_since_epoch`]), used to stamp the new
+    /// account's shadow last-change date.
+    pub fn build(self, today: u32) -> UserRecord {
+        let full_name = self.gecos.unwrap_or_else(|| self.name.clone());
+
+        let mut shadow = ShadowEntry {
+            name: self.name.clone(),
+            passwd: Password::Locked,
+            last_change_date: Some(today),
+            min_password_age: None,
+            max_password_age: None,
+            warn_period: None,
+            inact_period: None,
+            expire_date: None,
+            raw_tail: None,
+        };
+        match self.password {
+            PasswordSpec::Locked => {}
+            PasswordSpec::Hash(hash) => shadow.passwd = Password::Hash(hash),
+            PasswordSpec::PlainText(plaintext) => shadow.set_password(&plaintext, None, None),
+        }
+
+        UserRecord {
+            passwd: PasswdEntry {
+                name: self.name.clone(),
+                passwd: Password::Shadowed,
+                uid: self.uid,
+                gid: self.gid,
+                gecos: Gecos {
+                    full_name,
+                    ..Default::default()
+                },
+                home_dir: self.home_dir,
+                shell: self.shell.unwrap_or_else(|| DEFAULT_SHELL.to_owned()),
+            },
+            shadow: Some(shadow),
+            groups: std::iter::once(GroupEntry {
+                name: self.group_name,
+                passwd: "x".to_owned(),
+                gid: self.gid,
+                members: Vec::new(),
+            })
+            .chain(
+                self.supplementary_groups
+                    .into_iter()
+                    .map(|(group_name, gid)| GroupEntry {
+                        name: group_name,
+                        passwd: "x".to_owned(),
+                        gid,
+                        members: vec![self.name.clone()],
+                    }),
+            )
+            .collect(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// UserRead / UserDBWrite
+
+pub trait UserRead {
+    /// Looks up a user by name, joining its passwd/shadow/group rows.
+    fn get_user_by_name(&self, name: &str) -> Result<Option<UserRecord>, DbError>;
+
+    /// Looks up a user by uid, joining its passwd/shadow/group rows.
+    fn get_user_by_uid(&self, uid: u32) -> Result<Option<UserRecord>, DbError>;
+}
+
+pub trait UserDBWrite {
+    /// Provisions a new account by writing its passwd row, its shadow row
+    /// (if any), and its group rows, with matching uid/gid kept consistent
+    /// across all three files.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DbError::NotShadowed`] if `user.shadow` is `Some` but
+    /// `user.passwd.passwd` is not [`Password::Shadowed`], since a shadowed account must not
+    /// also carry a password hash in `/etc/passwd`.
+    fn add_user(&self, user: &UserRecord) -> Result<(), DbError>;
+}
+
+impl UserRead for Files {
+    fn get_user_by_name(&self, name: &str) -> Result<Option<UserRecord>, DbError> {
+        let passwd = match Files::read_all::<PasswdEntry>(&self.passwd)?
+            .into_iter()
+            .find(|p| p.name == name)
+        {
+            Some(passwd) => passwd,
+            None => return Ok(None),
+        };
+
+        let shadow = Files::read_all::<ShadowEntry>(&self.shadow)?
+            .into_iter()
+            .find(|s| s.name == name);
+
+        let groups = Files::read_all::<GroupEntry>(&self.group)?
+            .into_iter()
+            .filter(|g| g.members.iter().any(|m| m == name))
+            .collect();
+
+        Ok(Some(UserRecord {
+            passwd,
+            shadow,
+            groups,
+        }))
+    }
+
+    fn get_user_by_uid(&self, uid: u32) -> Result<Option<UserRecord>, DbError> {
+        let name = Files::read_all::<PasswdEntry>(&self.passwd)?
+            .into_iter()
+            .find(|p| p.uid == uid)
+            .map(|p| p.name);
+        match name {
+            Some(name) => self.get_user_by_name(&name),
+            None => Ok(None),
+        }
+    }
+}
+
+impl UserDBWrite for Files {
+    fn add_user(&self, user: &UserRecord) -> Result<(), DbError> {
+        if user.shadow.is_some() && user.passwd.passwd != Password::Shadowed {
+            return Err(DbError::NotShadowed(user.passwd.name.clone()));
+        }
+
+        let file = open_read_append(&self.passwd)?;
+        PasswdFileWriter::new(file).write(&user.passwd)?;
+
+        if let Some(shadow) = &user.shadow {
+            let file = open_read_append(&self.shadow)?;
+            ShadowFileWriter::new(file).write(shadow)?;
+        }
+
+        for group in &user.groups {
+            let file = open_read_append(&self.group)?;
+            GroupFileWriter::new(file).write(group)?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+
+    fn write_temp(contents: &str) -> tempfile::NamedTempFile {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        write!(f, "{contents}").unwrap();
+        f
+    }
+
+    fn sample_files() -> (
+        tempfile::NamedTempFile,
+        tempfile::NamedTempFile,
+        tempfile::NamedTempFile,
+        Files,
+    ) {
+        let passwd = write_temp("joe:x:1234:5678:Joe Blow:/home/joe:/bin/bash\n");
+        let group = write_temp("devs:x:5678:joe,moe\nothers:x:9999:joe\n");
+        let shadow = write_temp("joe:!:18881:0:99999:7:::\n");
+        let files = Files::new(
+            passwd.path().to_owned(),
+            group.path().to_owned(),
+            shadow.path().to_owned(),
+        );
+        (passwd, group, shadow, files)
+    }
+
+    #[test]
+    fn get_user_by_name_joins_shadow_and_groups() {
+        let (_passwd, _group, _shadow, files) = sample_files();
+        let user = files.get_user_by_name("joe").unwrap().unwrap();
+        assert_eq!(user.passwd.uid, 1234);
+        assert_eq!(user.shadow.unwrap().passwd, Password::Locked);
+        assert_eq!(user.groups.len(), 2);
+    }
+
+    #[test]
+    fn get_user_by_uid_works() {
+        let (_passwd, _group, _shadow, files) = sample_files();
+        let user = files.get_user_by_uid(1234).unwrap().unwrap();
+        assert_eq!(user.passwd.name, "joe");
+    }
+
+    #[test]
+    fn get_user_by_name_missing_returns_none() {
+        let (_passwd, _group, _shadow, files) = sample_files();
+        assert!(files.get_user_by_name("nobody").unwrap().is_none());
+    }
+
+    #[test]
+    fn user_spec_build_fills_in_defaults() {
+        let user = UserSpec::builder("moe", 2000, 2000, "moe", "/home/moe").build(18881);
+
+        assert_eq!(user.passwd.name, "moe");
+        assert_eq!(user.passwd.passwd, Password::Shadowed);
+        assert_eq!(user.passwd.uid, 2000);
+        assert_eq!(user.passwd.gid, 2000);
+        assert_eq!(user.passwd.gecos.full_name, "moe");
+        assert_eq!(user.passwd.home_dir, "/home/moe");
+        assert_eq!(user.passwd.shell, DEFAULT_SHELL);
+
+        let shadow = user.shadow.unwrap();
+        assert_eq!(shadow.passwd, Password::Locked);
+        assert_eq!(shadow.last_change_date, Some(18881));
+
+        assert_eq!(user.groups.len(), 1);
+        assert_eq!(user.groups[0].name, "moe");
+        assert_eq!(user.groups[0].gid, 2000);
+    }
+
+    #[test]
+    fn user_spec_build_honors_overrides() {
+        let user = UserSpec::builder("moe", 2000, 2000, "devs", "/home/moe")
+            .shell("/bin/zsh")
+            .gecos("Moe Howard")
+            .build(18881);
+
+        assert_eq!(user.passwd.shell, "/bin/zsh");
+        assert_eq!(user.passwd.gecos.full_name, "Moe Howard");
+        assert_eq!(user.groups[0].name, "devs");
+    }
+
+    #[test]
+    fn user_spec_build_adds_supplementary_groups() {
+        let user = UserSpec::builder("moe", 2000, 2000, "moe", "/home/moe")
+            .supplementary_group("docker", 999)
+            .supplementary_group("video", 44)
+            .build(18881);
+
+        assert_eq!(user.groups.len(), 3);
+        assert_eq!(user.groups[0].name, "moe");
+        assert_eq!(user.groups[0].members, Vec::<String>::new());
+        assert_eq!(user.groups[1].name, "docker");
+        assert_eq!(user.groups[1].gid, 999);
+        assert_eq!(user.groups[1].members, crate::string_vec!["moe"]);
+        assert_eq!(user.groups[2].name, "video");
+        assert_eq!(user.groups[2].gid, 44);
+        assert_eq!(user.groups[2].members, crate::string_vec!["moe"]);
+    }
+
+    #[test]
+    fn user_spec_build_defaults_to_locked_password() {
+        let user = UserSpec::builder("moe", 2000, 2000, "moe", "/home/moe").build(18881);
+        assert_eq!(user.shadow.unwrap().passwd, Password::Locked);
+    }
+
+    #[test]
+    fn user_spec_build_honors_password_hash() {
+        let user = UserSpec::builder("moe", 2000, 2000, "moe", "/home/moe")
+            .password_hash("$6$salt$abc")
+            .build(18881);
+        assert_eq!(
+            user.shadow.unwrap().passwd,
+            Password::Hash("$6$salt$abc".to_string())
+        );
+    }
+
+    #[test]
+    fn user_spec_build_hashes_plaintext_password() {
+        let user = UserSpec::builder("moe", 2000, 2000, "moe", "/home/moe")
+            .password("hunter2")
+            .build(18881);
+        let shadow = user.shadow.unwrap();
+        assert!(shadow.verify_password("hunter2"));
+        assert!(!shadow.verify_password("wrong"));
+    }
+
+    #[test]
+    fn add_user_writes_all_three_files() {
+        let (passwd, group, shadow, files) = sample_files();
+
+        let user = UserRecord {
+            passwd: PasswdEntry {
+                name: "moe".to_string(),
+                passwd: Password::Shadowed,
+                uid: 2000,
+                gid: 2000,
+                gecos: crate::passwd::Gecos {
+                    full_name: "Moe Howard".to_string(),
+                    ..Default::default()
+                },
+                home_dir: "/home/moe".to_string(),
+                shell: "/bin/bash".to_string(),
+            },
+            shadow: Some(ShadowEntry {
+                name: "moe".to_string(),
+                passwd: Password::Locked,
+                last_change_date: Some(18881),
+                min_password_age: None,
+                max_password_age: None,
+                warn_period: None,
+                inact_period: None,
+                expire_date: None,
+                raw_tail: None,
+            }),
+            groups: vec![GroupEntry {
+                name: "moe".to_string(),
+                passwd: "x".to_string(),
+                gid: 2000,
+                members: Vec::new(),
+            }],
+        };
+        files.add_user(&user).unwrap();
+
+        let added = files.get_user_by_name("moe").unwrap().unwrap();
+        assert_eq!(added.passwd.uid, 2000);
+        assert!(added.shadow.is_some());
+        assert_eq!(added.groups.len(), 1);
+
+        drop(passwd);
+        drop(group);
+        drop(shadow);
+    }
+
+    #[test]
+    fn add_user_rejects_unshadowed_passwd_field() {
+        let (_passwd, _group, _shadow, files) = sample_files();
+        let user = UserRecord {
+            passwd: PasswdEntry {
+                name: "larry".to_string(),
+                passwd: Password::Hash("notx".to_string()),
+                uid: 2001,
+                gid: 2001,
+                gecos: crate::passwd::Gecos {
+                    full_name: "Larry Fine".to_string(),
+                    ..Default::default()
+                },
+                home_dir: "/home/larry".to_string(),
+                shell: "/bin/bash".to_string(),
+            },
+            shadow: Some(ShadowEntry {
+                name: "larry".to_string(),
+                passwd: Password::Locked,
+                last_change_date: None,
+                min_password_age: None,
+                max_password_age: None,
+                warn_period: None,
+                inact_period: None,
+                expire_date: None,
+                raw_tail: None,
+            }),
+            groups: Vec::new(),
+        };
+        assert!(matches!(
+            files.add_user(&user),
+            Err(DbError::NotShadowed(name)) if name == "larry"
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
 use thiserror::Error;
 
@@ -8,6 +8,19 @@ use crate::util::short_write;
 pub trait Entry: Sized {
     fn from_line(line: &str) -> Result<Self, ReadEntryError>;
     fn to_line(&self) -> String;
+
+    /// The field used to look up and replace existing rows by identity
+    /// (e.g. `name` for passwd/group/shadow).
+    fn key(&self) -> &str;
+
+    /// Returns true if `self` and `other` share the same [`key`](Entry::key)
+    /// but represent conflicting identities (e.g. same name, different uid).
+    /// Used by [`EntFile::insert_or_replace`] to detect when a new entry
+    /// would silently clobber an existing row that doesn't actually belong
+    /// to the same account.
+    fn conflicts_with(&self, _other: &Self) -> bool {
+        false
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -63,6 +76,20 @@ impl<'a> EntLineParser<'a> {
             ))
         }
     }
+
+    /// Consumes and returns every remaining, unconsumed column, rejoined
+    /// with `:`, or `None` if the line held exactly the fields already
+    /// read and nothing more. Lets an [`Entry`] preserve columns it
+    /// doesn't model (e.g. shadow(5)'s reserved 9th field) verbatim
+    /// through a read/write round-trip.
+    pub fn remaining_str(&mut self) -> Option<String> {
+        let rest: Vec<&str> = self.fields.by_ref().collect();
+        if rest.is_empty() {
+            None
+        } else {
+            Some(rest.join(":"))
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -150,12 +177,166 @@ impl<T: Entry> EntFileWriter<T> {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// JsonEntFileError
+
+#[derive(Error, Debug)]
+pub enum JsonEntFileError {
+    #[error("error reading/writing entry file")]
+    Io(#[from] std::io::Error),
+
+    #[error("error (de)serializing entries as JSON")]
+    Json(#[from] serde_json::Error),
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// JsonEntFileReader / JsonEntFileWriter
+
+/// Reads a whole `Vec<T>` from a file holding a JSON array, as an
+/// alternative to the colon-delimited NSS line format read by
+/// [`EntFileReader`].
+pub struct JsonEntFileReader<T> {
+    file: File,
+    marker: PhantomData<T>, // T must be used
+}
+
+impl<T: serde::de::DeserializeOwned> JsonEntFileReader<T> {
+    pub fn new(file: File) -> JsonEntFileReader<T> {
+        JsonEntFileReader {
+            file,
+            marker: PhantomData,
+        }
+    }
+
+    /// Reads and parses the entire file as a JSON array of `T`.
+    pub fn read_all(mut self) -> Result<Vec<T>, JsonEntFileError> {
+        let mut buf = String::new();
+        self.file.read_to_string(&mut buf)?;
+        Ok(serde_json::from_str(&buf)?)
+    }
+}
+
+/// Writes a whole `Vec<T>` to a file as a JSON array, as an alternative to
+/// the colon-delimited NSS line format written by [`EntFileWriter`].
+pub struct JsonEntFileWriter<T> {
+    file: File,
+    marker: PhantomData<T>, // T must be used
+}
+
+impl<T: serde::Serialize> JsonEntFileWriter<T> {
+    pub fn new(file: File) -> JsonEntFileWriter<T> {
+        JsonEntFileWriter {
+            file,
+            marker: PhantomData,
+        }
+    }
+
+    /// Serializes `entries` as a pretty-printed JSON array and writes it,
+    /// replacing the file's existing contents.
+    pub fn write_all(&mut self, entries: &[T]) -> Result<(), JsonEntFileError> {
+        let json = serde_json::to_string_pretty(entries)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.set_len(0)?;
+        self.file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// UpsertError
+
+#[derive(Error, Debug)]
+pub enum UpsertError {
+    #[error("error reading entry from file")]
+    Read(#[from] ReadEntryError),
+
+    #[error("error writing entry file")]
+    Io(#[from] std::io::Error),
+
+    #[error("entry '{0}' already exists with a conflicting identity")]
+    Conflict(String),
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// EntFile
+
+/// An in-memory view of an ent-file (`/etc/passwd`-style) that supports
+/// idempotent upserts: repeated calls with the same key replace the row in
+/// place rather than appending a duplicate.
+pub struct EntFile<T> {
+    file: File,
+    entries: Vec<T>,
+}
+
+impl<T: Entry> EntFile<T> {
+    /// Reads every existing entry from `file` into memory.
+    pub fn open(file: File) -> Result<EntFile<T>, UpsertError> {
+        let mut reader = EntFileReader::new(file);
+        let mut entries = Vec::new();
+        for entry in &mut reader {
+            entries.push(entry?);
+        }
+        Ok(EntFile {
+            file: reader.into_inner(),
+            entries,
+        })
+    }
+
+    /// Returns the existing entry with the given [`key`](Entry::key), if any.
+    pub fn get(&self, key: &str) -> Option<&T> {
+        self.entries.iter().find(|e| e.key() == key)
+    }
+
+    /// Every entry currently held in memory, in file order.
+    pub fn entries(&self) -> &[T] {
+        &self.entries
+    }
+
+    /// Inserts `entry`, or replaces the existing row with the same
+    /// [`key`](Entry::key), preserving the ordering of untouched entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UpsertError::Conflict`] if an existing row has the same key
+    /// but [`Entry::conflicts_with`] reports it as a different identity
+    /// (e.g. same name, different uid), so a real system account is never
+    /// silently clobbered.
+    pub fn insert_or_replace(&mut self, entry: T) -> Result<(), UpsertError> {
+        match self.entries.iter().position(|e| e.key() == entry.key()) {
+            Some(pos) => {
+                if entry.conflicts_with(&self.entries[pos]) {
+                    return Err(UpsertError::Conflict(entry.key().to_owned()));
+                }
+                self.entries[pos] = entry;
+            }
+            None => self.entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// Truncates the file and rewrites every entry, in order.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.set_len(0)?;
+        for entry in &self.entries {
+            let line = entry.to_line() + "\n";
+            let data = line.as_bytes();
+            let written = self.file.write(data)?;
+            if written != data.len() {
+                return Err(short_write());
+            }
+        }
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // tests
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Read;
 
     #[test]
     fn next_field_str_works() -> Result<(), String> {
@@ -198,4 +379,166 @@ mod tests {
         assert_eq!(parser.next_field_u32_opt().unwrap(), None);
         Ok(())
     }
+
+    #[test]
+    fn remaining_str_is_none_when_nothing_left() {
+        let mut parser = EntLineParser::new("aaa:bbb");
+        parser.next_field_str().unwrap();
+        parser.next_field_str().unwrap();
+        assert_eq!(parser.remaining_str(), None);
+    }
+
+    #[test]
+    fn remaining_str_collects_unconsumed_columns() {
+        let mut parser = EntLineParser::new("aaa:bbb:ccc:");
+        parser.next_field_str().unwrap();
+        assert_eq!(parser.remaining_str(), Some("bbb:ccc:".to_string()));
+    }
+
+    #[derive(Debug, Eq, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+    struct TestEntry {
+        name: String,
+        id: u32,
+    }
+
+    impl Entry for TestEntry {
+        fn from_line(line: &str) -> Result<Self, ReadEntryError> {
+            let mut parser = EntLineParser::new(line);
+            Ok(TestEntry {
+                name: parser.next_field_string()?,
+                id: parser.next_field_u32()?,
+            })
+        }
+
+        fn to_line(&self) -> String {
+            format!("{}:{}", self.name, self.id)
+        }
+
+        fn key(&self) -> &str {
+            &self.name
+        }
+
+        fn conflicts_with(&self, other: &Self) -> bool {
+            self.id != other.id
+        }
+    }
+
+    fn write_entries(file: &mut File, lines: &[&str]) {
+        for line in lines {
+            writeln!(file, "{line}").unwrap();
+        }
+    }
+
+    #[test]
+    fn ent_file_appends_new_entry() {
+        let mut file = tempfile::tempfile().unwrap();
+        write_entries(&mut file, &["moe:1", "larry:2"]);
+        file.rewind().unwrap();
+
+        let mut ent_file = EntFile::<TestEntry>::open(file).unwrap();
+        ent_file
+            .insert_or_replace(TestEntry {
+                name: "shemp".to_string(),
+                id: 3,
+            })
+            .unwrap();
+        ent_file.flush().unwrap();
+
+        let mut file = ent_file.file;
+        file.rewind().unwrap();
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "moe:1\nlarry:2\nshemp:3\n");
+    }
+
+    #[test]
+    fn ent_file_replaces_existing_entry_in_place() {
+        let mut file = tempfile::tempfile().unwrap();
+        write_entries(&mut file, &["moe:1", "larry:2", "shemp:3"]);
+        file.rewind().unwrap();
+
+        let mut ent_file = EntFile::<TestEntry>::open(file).unwrap();
+        ent_file
+            .insert_or_replace(TestEntry {
+                name: "larry".to_string(),
+                id: 2,
+            })
+            .unwrap();
+        ent_file.flush().unwrap();
+
+        let mut file = ent_file.file;
+        file.rewind().unwrap();
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "moe:1\nlarry:2\nshemp:3\n");
+    }
+
+    #[test]
+    fn ent_file_get_finds_existing_entry_by_key() {
+        let mut file = tempfile::tempfile().unwrap();
+        write_entries(&mut file, &["moe:1", "larry:2"]);
+        file.rewind().unwrap();
+
+        let ent_file = EntFile::<TestEntry>::open(file).unwrap();
+        assert_eq!(ent_file.get("larry").unwrap().id, 2);
+        assert!(ent_file.get("shemp").is_none());
+    }
+
+    #[test]
+    fn ent_file_entries_lists_everything_in_file_order() {
+        let mut file = tempfile::tempfile().unwrap();
+        write_entries(&mut file, &["moe:1", "larry:2"]);
+        file.rewind().unwrap();
+
+        let ent_file = EntFile::<TestEntry>::open(file).unwrap();
+        let names: Vec<&str> = ent_file.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["moe", "larry"]);
+    }
+
+    #[test]
+    fn ent_file_rejects_conflicting_identity() {
+        let mut file = tempfile::tempfile().unwrap();
+        write_entries(&mut file, &["moe:1"]);
+        file.rewind().unwrap();
+
+        let mut ent_file = EntFile::<TestEntry>::open(file).unwrap();
+        let result = ent_file.insert_or_replace(TestEntry {
+            name: "moe".to_string(),
+            id: 99,
+        });
+        assert!(result.is_err_and(|e| matches!(e, UpsertError::Conflict(name) if name == "moe")));
+    }
+
+    #[test]
+    fn json_write_all_then_read_all_round_trips() {
+        let mut file = tempfile::tempfile().unwrap();
+        let entries = vec![
+            TestEntry {
+                name: "moe".to_string(),
+                id: 1,
+            },
+            TestEntry {
+                name: "larry".to_string(),
+                id: 2,
+            },
+        ];
+
+        JsonEntFileWriter::new(file.try_clone().unwrap())
+            .write_all(&entries)
+            .unwrap();
+        file.rewind().unwrap();
+
+        let read_back = JsonEntFileReader::<TestEntry>::new(file).read_all().unwrap();
+        assert_eq!(read_back, entries);
+    }
+
+    #[test]
+    fn json_read_all_rejects_malformed_json() {
+        let mut file = tempfile::tempfile().unwrap();
+        write!(file, "not json").unwrap();
+        file.rewind().unwrap();
+
+        let result = JsonEntFileReader::<TestEntry>::new(file).read_all();
+        assert!(result.is_err_and(|e| matches!(e, JsonEntFileError::Json(_))));
+    }
 }
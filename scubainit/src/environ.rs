@@ -0,0 +1,99 @@
+/// The `PATH` applied when a reset clears the inherited environment.
+pub const DEFAULT_SECURE_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Variables preserved across a reset regardless of `keep`, since
+/// `change_user` sets them to the correct values once a user switch
+/// happens.
+const ALWAYS_KEPT: [&str; 3] = ["USER", "LOGNAME", "HOME"];
+
+/// Computes the sanitized environment to apply, given a snapshot of the
+/// current environment and a reset/keep/delete policy.
+///
+/// When `reset` is true, every inherited variable is dropped except those
+/// named in `keep` (plus `USER`/`LOGNAME`/`HOME`, always preserved), and
+/// `PATH` is set to [`DEFAULT_SECURE_PATH`]. When `reset` is false, every
+/// inherited variable is kept as-is. Either way, any variable named in
+/// `delete` is removed last.
+pub fn sanitize(
+    vars: Vec<(String, String)>,
+    reset: bool,
+    keep: &[String],
+    delete: &[String],
+) -> Vec<(String, String)> {
+    let mut result = if reset {
+        let mut baseline = vec![(String::from("PATH"), DEFAULT_SECURE_PATH.to_owned())];
+        for (name, value) in vars {
+            if name == "PATH" {
+                continue;
+            }
+            if ALWAYS_KEPT.contains(&name.as_str()) || keep.contains(&name) {
+                baseline.push((name, value));
+            }
+        }
+        baseline
+    } else {
+        vars
+    };
+
+    result.retain(|(name, _)| !delete.contains(name));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string_vec;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn no_reset_keeps_everything_by_default() {
+        let result = sanitize(vars(&[("FOO", "bar"), ("PATH", "/bin")]), false, &[], &[]);
+        assert_eq!(result, vars(&[("FOO", "bar"), ("PATH", "/bin")]));
+    }
+
+    #[test]
+    fn reset_drops_everything_but_path_and_always_kept() {
+        let result = sanitize(
+            vars(&[("FOO", "bar"), ("USER", "joe"), ("PATH", "/weird")]),
+            true,
+            &[],
+            &[],
+        );
+        assert_eq!(
+            result,
+            vars(&[("PATH", DEFAULT_SECURE_PATH), ("USER", "joe")])
+        );
+    }
+
+    #[test]
+    fn reset_preserves_keep_listed_vars() {
+        let result = sanitize(vars(&[("FOO", "bar")]), true, &string_vec!["FOO"], &[]);
+        assert_eq!(
+            result,
+            vars(&[("PATH", DEFAULT_SECURE_PATH), ("FOO", "bar")])
+        );
+    }
+
+    #[test]
+    fn delete_list_applies_regardless_of_reset() {
+        let result = sanitize(
+            vars(&[("FOO", "bar"), ("BAZ", "qux")]),
+            false,
+            &[],
+            &string_vec!["BAZ"],
+        );
+        assert_eq!(result, vars(&[("FOO", "bar")]));
+    }
+
+    #[test]
+    fn delete_list_applies_after_reset() {
+        let result = sanitize(vars(&[("USER", "joe")]), true, &[], &string_vec!["USER"]);
+        assert_eq!(result, vars(&[("PATH", DEFAULT_SECURE_PATH)]));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::entfiles::{EntFileReader, EntFileWriter, EntLineParser, Entry, ReadEntryError};
 use crate::util::split_csv_str;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct GroupEntry {
     pub name: String,
     pub passwd: String,
@@ -34,6 +34,43 @@ impl Entry for GroupEntry {
             self.members.join(","),
         )
     }
+
+    fn key(&self) -> &str {
+        &self.name
+    }
+
+    fn conflicts_with(&self, other: &Self) -> bool {
+        self.gid != other.gid
+    }
+}
+
+impl GroupEntry {
+    /// Adds `member` to this group's member list, if not already present.
+    /// Returns `true` if the member was added.
+    pub fn add_member(&mut self, member: &str) -> bool {
+        if self.members.iter().any(|m| m == member) {
+            false
+        } else {
+            self.members.push(member.to_owned());
+            true
+        }
+    }
+
+    /// Removes `member` from this group's member list. A no-op if the
+    /// member isn't present. Returns `true` if a member was removed.
+    pub fn remove_member(&mut self, member: &str) -> bool {
+        let before = self.members.len();
+        self.members.retain(|m| m != member);
+        self.members.len() != before
+    }
+}
+
+/// Returns every group in `groups` that lists `member` among its members.
+pub fn groups_containing<'a>(groups: &'a [GroupEntry], member: &str) -> Vec<&'a GroupEntry> {
+    groups
+        .iter()
+        .filter(|g| g.members.iter().any(|m| m == member))
+        .collect()
 }
 
 #[cfg(test)]
@@ -65,4 +102,56 @@ mod tests {
         assert_eq!(line, SAMPLE_LINE);
         Ok(())
     }
+
+    #[test]
+    fn add_member_appends_new_member() {
+        let mut ent = get_sample_ent();
+        assert!(ent.add_member("curly"));
+        assert_eq!(ent.members, string_vec!["moe", "larry", "shemp", "curly"]);
+    }
+
+    #[test]
+    fn add_member_is_idempotent() {
+        let mut ent = get_sample_ent();
+        assert!(!ent.add_member("moe"));
+        assert_eq!(ent.members, string_vec!["moe", "larry", "shemp"]);
+    }
+
+    #[test]
+    fn remove_member_drops_existing_member() {
+        let mut ent = get_sample_ent();
+        assert!(ent.remove_member("larry"));
+        assert_eq!(ent.members, string_vec!["moe", "shemp"]);
+    }
+
+    #[test]
+    fn remove_member_is_a_noop_when_absent() {
+        let mut ent = get_sample_ent();
+        assert!(!ent.remove_member("curly"));
+        assert_eq!(ent.members, string_vec!["moe", "larry", "shemp"]);
+    }
+
+    #[test]
+    fn groups_containing_finds_matching_groups() {
+        let groups = vec![
+            get_sample_ent(),
+            GroupEntry {
+                name: "bar".to_string(),
+                passwd: "x".to_string(),
+                gid: 2345,
+                members: string_vec!["moe"],
+            },
+            GroupEntry {
+                name: "baz".to_string(),
+                passwd: "x".to_string(),
+                gid: 3456,
+                members: Vec::new(),
+            },
+        ];
+        let found = groups_containing(&groups, "moe");
+        assert_eq!(
+            found.iter().map(|g| g.name.as_str()).collect::<Vec<_>>(),
+            vec!["foo", "bar"]
+        );
+    }
 }
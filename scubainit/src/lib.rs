@@ -1,6 +1,10 @@
-mod entfiles;
+pub mod db;
+pub mod entfiles;
+pub mod environ;
 pub mod groups;
+pub mod password;
 pub mod passwd;
+mod sha512crypt;
 pub mod shadow;
 pub mod util;
 
@@ -2,14 +2,19 @@ use anyhow::{bail, Context as _, Result};
 use exec::execvp;
 use log::{debug, error, info, warn};
 use std::env;
+use std::ffi::CString;
 use std::fs;
 use std::os::unix::fs::{chown, PermissionsExt};
 use std::os::unix::process::ExitStatusExt;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::process::{Command, ExitCode};
 use stderrlog::{self, LogLevelNum};
 
-use scubainit::util::{libc_result, make_executable, open_read_append};
+use scubainit::db::{UserRecord, UserSpec};
+use scubainit::entfiles::EntFile;
+use scubainit::environ;
+use scubainit::util::today_days_since_epoch;
+use scubainit::util::{libc_result, make_executable, split_csv_str, FileLock};
 use scubainit::util::{pop_env_bool, pop_env_str, pop_env_uint};
 use scubainit::{groups, passwd, shadow};
 
@@ -18,14 +23,23 @@ const SCUBAINIT_EXIT_FAIL: u8 = 99;
 const ETC_PASSWD: &str = "/etc/passwd";
 const ETC_GROUP: &str = "/etc/group";
 const ETC_SHADOW: &str = "/etc/shadow";
-const INVALID_PASSWORD: &str = "x";
-const DEFAULT_SHELL: &str = "/bin/bash";
 
 const SCUBAINIT_UID: &str = "SCUBAINIT_UID";
 const SCUBAINIT_GID: &str = "SCUBAINIT_GID";
 const SCUBAINIT_UMASK: &str = "SCUBAINIT_UMASK";
 const SCUBAINIT_USER: &str = "SCUBAINIT_USER";
 const SCUBAINIT_GROUP: &str = "SCUBAINIT_GROUP";
+const SCUBAINIT_GROUPS: &str = "SCUBAINIT_GROUPS";
+const SCUBAINIT_PASSWD_HASH: &str = "SCUBAINIT_PASSWD_HASH";
+const SCUBAINIT_PASSWD: &str = "SCUBAINIT_PASSWD";
+const SCUBAINIT_NO_NEW_PRIVS: &str = "SCUBAINIT_NO_NEW_PRIVS";
+const SCUBAINIT_ENV_RESET: &str = "SCUBAINIT_ENV_RESET";
+const SCUBAINIT_ENV_KEEP: &str = "SCUBAINIT_ENV_KEEP";
+const SCUBAINIT_ENV_DELETE: &str = "SCUBAINIT_ENV_DELETE";
+
+/// Variables removed from the inherited environment unconditionally, in
+/// addition to whatever `SCUBAINIT_ENV_DELETE` lists.
+const DEFAULT_ENV_DELETE: [&str; 3] = ["PWD", "OLDPWD", "XAUTHORITY"];
 const SCUBAINIT_HOOK_USER: &str = "SCUBAINIT_HOOK_USER";
 const SCUBAINIT_HOOK_ROOT: &str = "SCUBAINIT_HOOK_ROOT";
 const SCUBAINIT_VERBOSE: &str = "SCUBAINIT_VERBOSE";
@@ -69,6 +83,8 @@ fn run_scubainit() -> Result<()> {
         ctx.call_user_hook()?;
     }
 
+    ctx.apply_no_new_privs()?;
+
     let argv = &env::args_os().skip(1).collect::<Vec<_>>();
     if argv.is_empty() {
         bail!("Missing command");
@@ -82,80 +98,97 @@ fn run_scubainit() -> Result<()> {
 }
 
 struct UserInfo {
-    uid: u32,
-    gid: u32,
-    user: String,
-    group: String,
+    record: UserRecord,
 }
 
 impl UserInfo {
-    pub fn home_dir(&self) -> PathBuf {
-        Path::new(USER_HOME).join(&self.user)
+    pub fn home_dir(&self) -> &str {
+        &self.record.passwd.home_dir
     }
 
     pub fn make_homedir(&self) -> Result<()> {
         let home = self.home_dir();
         debug!("Creating home dir: {home:?}");
-        fs::create_dir_all(&home)?;
-        fs::set_permissions(&home, fs::Permissions::from_mode(0o700))?;
-        chown(&home, Some(self.uid), Some(self.gid))?;
+        fs::create_dir_all(home)?;
+        fs::set_permissions(home, fs::Permissions::from_mode(0o700))?;
+        chown(
+            home,
+            Some(self.record.passwd.uid),
+            Some(self.record.passwd.gid),
+        )?;
         Ok(())
     }
 
+    /// Ensures every group in `self.record.groups` (the primary group, plus
+    /// any supplementary groups) exists in `/etc/group`, and lists the
+    /// members that group carries in the record (the user, for each
+    /// supplementary group; none for the primary group). An existing
+    /// group with the same name is reused (and merged with) rather than
+    /// duplicated, as long as its gid matches.
     pub fn add_group(&self) -> Result<()> {
-        let group_name = &self.group;
-        let gid = self.gid;
-
-        debug!("Adding group '{group_name}' (gid={gid})");
-
-        let file = open_read_append(ETC_GROUP)?;
-
-        // Try to find a conflicting group (one matching name or gid).
-        let mut reader = groups::GroupFileReader::new(file);
-        for grp in &mut reader {
-            let grp = grp?;
-            let name_matches = grp.name.as_str() == group_name;
-            let gid_matches = grp.gid == gid;
-
-            if name_matches {
-                if gid_matches {
-                    // Identical name+gid exists; surprising, but no problem
-                    return Ok(());
+        // Held for the whole read-scan-append transaction; dropped (and
+        // thus unlocked) when this function returns.
+        let lock = FileLock::open_read_append(ETC_GROUP)?;
+        let mut ent_file = EntFile::<groups::GroupEntry>::open(lock.try_clone()?)?;
+
+        for want in &self.record.groups {
+            match ent_file.get(&want.name) {
+                Some(existing) => {
+                    if existing.gid != want.gid {
+                        bail!(
+                            "Group {} already exists with different gid in {ETC_GROUP}",
+                            want.name
+                        );
+                    }
+                    let mut merged = existing.clone();
+                    let mut added = false;
+                    for member in &want.members {
+                        added |= merged.add_member(member);
+                    }
+                    if added {
+                        debug!(
+                            "Adding member(s) to existing group '{}' (gid={})",
+                            want.name, want.gid
+                        );
+                        ent_file.insert_or_replace(merged)?;
+                    }
+                }
+                None => {
+                    if let Some(conflict) = ent_file
+                        .entries()
+                        .iter()
+                        .find(|g| g.gid == want.gid && g.name != want.name)
+                    {
+                        warn!(
+                            "Warning: GID {} already exists in {ETC_GROUP} (as group '{}')",
+                            want.gid, conflict.name
+                        );
+                    }
+                    debug!("Adding group '{}' (gid={})", want.name, want.gid);
+                    ent_file.insert_or_replace(want.clone())?;
                 }
-                bail!("Group {group_name} already exists with different gid in {ETC_GROUP}");
-            }
-
-            if gid_matches {
-                warn!("Warning: GID {gid} already exists in {ETC_GROUP}");
             }
         }
 
-        let file = reader.into_inner();
-
-        // Okay, add group
-        let grp = groups::GroupEntry {
-            name: group_name.to_owned(),
-            passwd: INVALID_PASSWORD.to_owned(),
-            gid,
-            members: Vec::new(),
-        };
-        let mut writer = groups::GroupFileWriter::new(file);
-        Ok(writer.write(&grp)?)
+        Ok(ent_file.flush()?)
     }
 
     pub fn add_user(&self) -> Result<()> {
-        let user_name = &self.user;
-        let uid = self.uid;
+        let pwd = &self.record.passwd;
+        let user_name = &pwd.name;
+        let uid = pwd.uid;
         debug!("Adding user '{user_name}' (uid={uid})");
 
-        let file = open_read_append(ETC_PASSWD)?;
+        // Held for the whole read-scan-append transaction; dropped (and
+        // thus unlocked) when this function returns.
+        let lock = FileLock::open_read_append(ETC_PASSWD)?;
 
         // Try to find a conflicting user (one matching name or uid).
-        let mut reader = passwd::PasswdFileReader::new(file);
-        for pwd in &mut reader {
-            let pwd = pwd?;
-            let name_matches = pwd.name.as_str() == user_name;
-            let uid_matches = pwd.uid == uid;
+        let mut reader = passwd::PasswdFileReader::new(lock.try_clone()?);
+        for existing in &mut reader {
+            let existing = existing?;
+            let name_matches = existing.name == *user_name;
+            let uid_matches = existing.uid == uid;
 
             if name_matches {
                 if uid_matches {
@@ -173,32 +206,28 @@ impl UserInfo {
         let file = reader.into_inner();
 
         // Okay, add user
-        let home_dir_path = self.home_dir();
-        let home_dir_str = home_dir_path.to_str().context("Invalid home_dir")?;
-        let user = passwd::PasswdEntry {
-            name: user_name.to_owned(),
-            passwd: INVALID_PASSWORD.to_owned(),
-            uid,
-            gid: self.gid,
-            gecos: user_name.to_owned(),
-            home_dir: home_dir_str.to_owned(),
-            shell: DEFAULT_SHELL.to_owned(),
-        };
         let mut writer = passwd::PasswdFileWriter::new(file);
-        Ok(writer.write(&user)?)
+        Ok(writer.write(pwd)?)
     }
 
     pub fn add_shadow(&self) -> Result<()> {
-        let user_name = &self.user;
+        let entry = self
+            .record
+            .shadow
+            .as_ref()
+            .context("UserSpec always produces a shadow entry")?;
+        let user_name = &entry.name;
         debug!("Adding shadow entry for '{user_name}'");
 
-        let file = open_read_append(ETC_SHADOW)?;
+        // Held for the whole read-scan-append transaction; dropped (and
+        // thus unlocked) when this function returns.
+        let lock = FileLock::open_read_append(ETC_SHADOW)?;
 
         // Try to find a conflicting user (one matching name).
-        let mut reader = shadow::ShadowFileReader::new(file);
+        let mut reader = shadow::ShadowFileReader::new(lock.try_clone()?);
         for sp in &mut reader {
             let sp = sp?;
-            if sp.name.as_str() == user_name {
+            if sp.name == *user_name {
                 // Already exists; we don't really care about its values
                 return Ok(());
             }
@@ -207,32 +236,23 @@ impl UserInfo {
         let file = reader.into_inner();
 
         // Okay, add shadow entry
-        let entry = shadow::ShadowEntry {
-            name: user_name.to_owned(),
-            passwd: INVALID_PASSWORD.to_owned(),
-            last_change_date: None,
-            min_password_age: None,
-            max_password_age: None,
-            warn_period: None,
-            inact_period: None,
-            expire_date: None,
-        };
         let mut writer = shadow::ShadowFileWriter::new(file);
-        Ok(writer.write(&entry)?)
+        Ok(writer.write(entry)?)
     }
 
     pub fn change_user(&self) -> Result<()> {
-        let uid = self.uid;
-        let gid = self.gid;
-        let user = &self.user;
+        let uid = self.record.passwd.uid;
+        let gid = self.record.passwd.gid;
+        let user = &self.record.passwd.name;
         debug!("Changing to user={user}, uid={uid}, gid={gid}");
 
-        // Drop all supplementary groups. Must be called before setuid().
-        // SAFETY: The setgroups() syscall accesses no memory when size is 0.
-        //         Calling setgroups(0, NULL) is explicitly supported.
+        // Populate the supplementary group set from the group membership
+        // lines add_group() just wrote to /etc/group. Must be called after
+        // those writes land, and before setgid()/setuid().
+        let user_cstr = CString::new(user.as_str()).context("Invalid user name")?;
         unsafe {
-            libc_result(libc::setgroups(0, std::ptr::null()))?;
-        };
+            libc_result(libc::initgroups(user_cstr.as_ptr(), gid))?;
+        }
 
         // Change group id. Must be called before setguid().
         // SAFETY: The setgid() syscall uses only its single integer argument.
@@ -247,11 +267,10 @@ impl UserInfo {
         }
 
         // Set other environment variables related to the new user.
-        let home_dir_path = self.home_dir();
-        let home_dir_str = home_dir_path.to_str().context("Invalid home_dir")?;
+        let home_dir = self.home_dir();
         env::set_var("USER", user);
         env::set_var("LOGNAME", user);
-        env::set_var("HOME", home_dir_str);
+        env::set_var("HOME", home_dir);
 
         Ok(())
     }
@@ -262,6 +281,7 @@ struct Context {
     umask: Option<u32>,
     user_hook: Option<String>,
     root_hook: Option<String>,
+    no_new_privs: bool,
 }
 
 impl Context {
@@ -282,6 +302,42 @@ impl Context {
         self.call_hook(&self.user_hook)
     }
 
+    /// If `SCUBAINIT_NO_NEW_PRIVS` was set, sets `PR_SET_NO_NEW_PRIVS` so no
+    /// setuid/setgid binary the command later execs can gain privileges, and
+    /// clears the capability bounding set so even a root-in-namespace
+    /// command can't regain dropped capabilities. A no-op otherwise.
+    ///
+    /// Must be called right before exec; once set, `no_new_privs` is
+    /// irreversible for this process and all its children.
+    pub fn apply_no_new_privs(&self) -> Result<()> {
+        if !self.no_new_privs {
+            return Ok(());
+        }
+
+        debug!("Setting PR_SET_NO_NEW_PRIVS");
+        // SAFETY: prctl(PR_SET_NO_NEW_PRIVS, ...) takes no pointer arguments.
+        unsafe {
+            libc_result(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+        }
+
+        debug!("Dropping all capabilities from the bounding set");
+        for cap in 0..=63 {
+            // SAFETY: prctl(PR_CAPBSET_DROP, ...) takes no pointer arguments.
+            let rc = unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) };
+            if rc == -1 {
+                let err = std::io::Error::last_os_error();
+                // EINVAL means `cap` is past this kernel's highest known
+                // capability; everything valid has already been dropped.
+                if err.raw_os_error() == Some(libc::EINVAL) {
+                    break;
+                }
+                return Err(err.into());
+            }
+        }
+
+        Ok(())
+    }
+
     fn call_hook(&self, path_str: &Option<String>) -> Result<()> {
         let Some(path) = path_str else {
             return Ok(());
@@ -304,6 +360,22 @@ impl Context {
     }
 }
 
+/// Parses `SCUBAINIT_GROUPS`' comma-separated `name:gid` pairs.
+fn parse_supplementary_groups(value: &str) -> Result<Vec<(String, u32)>> {
+    split_csv_str(value)
+        .into_iter()
+        .map(|pair| {
+            let (name, gid) = pair.split_once(':').with_context(|| {
+                format!("Invalid {SCUBAINIT_GROUPS} entry {pair:?}, expected name:gid")
+            })?;
+            let gid: u32 = gid
+                .parse()
+                .with_context(|| format!("Invalid gid in {SCUBAINIT_GROUPS} entry {pair:?}"))?;
+            Ok((name.to_owned(), gid))
+        })
+        .collect()
+}
+
 fn process_envvars_user_info() -> Result<Option<UserInfo>> {
     // The following variables are optional, but if any is set, all must be set:
     let uid = pop_env_uint(SCUBAINIT_UID)?;
@@ -311,6 +383,16 @@ fn process_envvars_user_info() -> Result<Option<UserInfo>> {
     let user = pop_env_str(SCUBAINIT_USER);
     let group = pop_env_str(SCUBAINIT_GROUP);
 
+    // Optional even when a user is requested: supplementary group membership
+    // and an initial login password.
+    let groups = pop_env_str(SCUBAINIT_GROUPS);
+    let passwd_hash = pop_env_str(SCUBAINIT_PASSWD_HASH);
+    let passwd_plain = pop_env_str(SCUBAINIT_PASSWD);
+
+    if passwd_hash.is_some() && passwd_plain.is_some() {
+        bail!("At most one of {SCUBAINIT_PASSWD_HASH} or {SCUBAINIT_PASSWD} may be set.");
+    }
+
     let vars_some = [
         uid.is_some(),
         gid.is_some(),
@@ -318,14 +400,38 @@ fn process_envvars_user_info() -> Result<Option<UserInfo>> {
         group.is_some(),
     ];
     match vars_some.into_iter().filter(|b| *b).count() {
-        0 => Ok(None),
-        n if n == vars_some.len() => Ok(Some(UserInfo {
+        0 => {
+            if groups.is_some() {
+                bail!("{SCUBAINIT_GROUPS} requires SCUBAINIT_{{UID,GID,USER,GROUP}} to also be set.");
+            }
+            if passwd_hash.is_some() || passwd_plain.is_some() {
+                bail!(
+                    "{SCUBAINIT_PASSWD_HASH}/{SCUBAINIT_PASSWD} require SCUBAINIT_{{UID,GID,USER,GROUP}} to also be set."
+                );
+            }
+            Ok(None)
+        }
+        n if n == vars_some.len() => {
             // unwrap() won't fail due to is_some() checks above
-            uid: uid.unwrap(),
-            gid: gid.unwrap(),
-            user: user.unwrap(),
-            group: group.unwrap(),
-        })),
+            let user = user.unwrap();
+            let home_dir = Path::new(USER_HOME).join(&user);
+            let home_dir = home_dir.to_str().context("Invalid home_dir")?;
+            let mut spec =
+                UserSpec::builder(user, uid.unwrap(), gid.unwrap(), group.unwrap(), home_dir);
+            if let Some(groups) = groups {
+                for (name, gid) in parse_supplementary_groups(&groups)? {
+                    spec = spec.supplementary_group(name, gid);
+                }
+            }
+            if let Some(hash) = passwd_hash {
+                spec = spec.password_hash(hash);
+            } else if let Some(plaintext) = passwd_plain {
+                spec = spec.password(plaintext);
+            }
+            Ok(Some(UserInfo {
+                record: spec.build(today_days_since_epoch()),
+            }))
+        }
         _ => {
             bail!("If any of SCUBAINIT_{{UID,GID,USER,GROUP}} are set, all must be set.");
         }
@@ -334,13 +440,7 @@ fn process_envvars_user_info() -> Result<Option<UserInfo>> {
 
 fn process_envvars() -> Result<Context> {
     // Get the environment variables from scuba.
-
-    // Clear out other env. vars
-    env::remove_var("PWD");
-    env::remove_var("OLDPWD");
-    env::remove_var("XAUTHORITY");
-
-    Ok(Context {
+    let ctx = Context {
         user_info: process_envvars_user_info()?,
 
         // Optional vars
@@ -351,7 +451,41 @@ fn process_envvars() -> Result<Context> {
         // Hook scripts
         user_hook: pop_env_str(SCUBAINIT_HOOK_USER),
         root_hook: pop_env_str(SCUBAINIT_HOOK_ROOT),
-    })
+
+        no_new_privs: pop_env_bool(SCUBAINIT_NO_NEW_PRIVS),
+    };
+
+    // Sanitize whatever's left, once every other SCUBAINIT_* variable has
+    // been popped off (so none of them leak into the container's shell).
+    sanitize_environment();
+
+    Ok(ctx)
+}
+
+/// Applies the `SCUBAINIT_ENV_{RESET,KEEP,DELETE}` policy to the current
+/// process environment.
+fn sanitize_environment() {
+    let reset = pop_env_bool(SCUBAINIT_ENV_RESET);
+    let keep = pop_env_str(SCUBAINIT_ENV_KEEP)
+        .map(|s| split_csv_str(&s))
+        .unwrap_or_default();
+    let extra_delete = pop_env_str(SCUBAINIT_ENV_DELETE)
+        .map(|s| split_csv_str(&s))
+        .unwrap_or_default();
+    let delete: Vec<String> = DEFAULT_ENV_DELETE
+        .iter()
+        .map(|s| s.to_string())
+        .chain(extra_delete)
+        .collect();
+
+    let sanitized = environ::sanitize(env::vars().collect(), reset, &keep, &delete);
+
+    for (name, _) in env::vars() {
+        env::remove_var(name);
+    }
+    for (name, value) in sanitized {
+        env::set_var(name, value);
+    }
 }
 
 fn setup_logging() -> Result<()> {
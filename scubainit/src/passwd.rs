@@ -1,12 +1,62 @@
 use crate::entfiles::{EntFileReader, EntFileWriter, EntLineParser, Entry, ReadEntryError};
+use crate::password::Password;
 
-#[derive(Debug, Eq, PartialEq)]
+////////////////////////////////////////////////////////////////////////////////
+// Gecos
+
+/// The comma-separated subfields of the passwd(5) GECOS field:
+/// full name, room number, work phone, home phone, and a catch-all `other`.
+#[derive(Debug, Default, Eq, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Gecos {
+    pub full_name: String,
+    pub room_number: String,
+    pub work_phone: String,
+    pub home_phone: String,
+    pub other: String,
+}
+
+impl Gecos {
+    /// Parses the raw GECOS field, splitting on `,`. Subfields beyond the
+    /// fourth are folded into `other`, and missing trailing subfields are
+    /// left empty.
+    pub fn parse(field: &str) -> Gecos {
+        let mut parts = field.splitn(5, ',');
+        Gecos {
+            full_name: parts.next().unwrap_or_default().to_owned(),
+            room_number: parts.next().unwrap_or_default().to_owned(),
+            work_phone: parts.next().unwrap_or_default().to_owned(),
+            home_phone: parts.next().unwrap_or_default().to_owned(),
+            other: parts.next().unwrap_or_default().to_owned(),
+        }
+    }
+
+    /// Re-serializes to the comma-joined form, omitting empty trailing
+    /// subfields so common single-field GECOS values round-trip byte-for-byte.
+    fn format(&self) -> String {
+        let fields = [
+            self.full_name.as_str(),
+            self.room_number.as_str(),
+            self.work_phone.as_str(),
+            self.home_phone.as_str(),
+            self.other.as_str(),
+        ];
+        match fields.iter().rposition(|f| !f.is_empty()) {
+            Some(last) => fields[..=last].join(","),
+            None => String::new(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// PasswdEntry
+
+#[derive(Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct PasswdEntry {
     pub name: String,
-    pub passwd: String,
+    pub passwd: Password,
     pub uid: u32,
     pub gid: u32,
-    pub gecos: String, // TODO: Vec<String> or struct
+    pub gecos: Gecos,
     pub home_dir: String,
     pub shell: String, // TODO: Option<String>
 }
@@ -21,10 +71,10 @@ impl Entry for PasswdEntry {
         let mut parser = EntLineParser::new(line);
         Ok(PasswdEntry {
             name: parser.next_field_string()?,
-            passwd: parser.next_field_string()?,
+            passwd: Password::from_field(parser.next_field_str()?),
             uid: parser.next_field_u32()?,
             gid: parser.next_field_u32()?,
-            gecos: parser.next_field_string()?,
+            gecos: Gecos::parse(parser.next_field_str()?),
             home_dir: parser.next_field_string()?,
             shell: parser.next_field_string()?,
         })
@@ -33,9 +83,23 @@ impl Entry for PasswdEntry {
     fn to_line(&self) -> String {
         format!(
             "{}:{}:{}:{}:{}:{}:{}",
-            self.name, self.passwd, self.uid, self.gid, self.gecos, self.home_dir, self.shell,
+            self.name,
+            self.passwd.to_field(),
+            self.uid,
+            self.gid,
+            self.gecos.format(),
+            self.home_dir,
+            self.shell,
         )
     }
+
+    fn key(&self) -> &str {
+        &self.name
+    }
+
+    fn conflicts_with(&self, other: &Self) -> bool {
+        self.uid != other.uid
+    }
 }
 
 #[cfg(test)]
@@ -47,10 +111,13 @@ mod tests {
     fn get_sample_ent() -> PasswdEntry {
         PasswdEntry {
             name: "joe".to_string(),
-            passwd: "x".to_string(),
+            passwd: Password::Shadowed,
             uid: 1234,
             gid: 5678,
-            gecos: "Joe Blow".to_string(),
+            gecos: Gecos {
+                full_name: "Joe Blow".to_string(),
+                ..Default::default()
+            },
             home_dir: "/home/joe".to_string(),
             shell: "/bin/bash".to_string(),
         }
@@ -69,4 +136,32 @@ mod tests {
         assert_eq!(line, SAMPLE_LINE);
         Ok(())
     }
+
+    #[test]
+    fn gecos_round_trips_all_subfields() {
+        const LINE: &str = "Joe Blow,Room 1,555-1111,555-2222,extra,fields";
+        let gecos = Gecos::parse(LINE);
+        assert_eq!(gecos.full_name, "Joe Blow");
+        assert_eq!(gecos.room_number, "Room 1");
+        assert_eq!(gecos.work_phone, "555-1111");
+        assert_eq!(gecos.home_phone, "555-2222");
+        assert_eq!(gecos.other, "extra,fields");
+        assert_eq!(gecos.format(), LINE);
+    }
+
+    #[test]
+    fn gecos_round_trips_empty_field() {
+        let gecos = Gecos::parse("");
+        assert_eq!(gecos, Gecos::default());
+        assert_eq!(gecos.format(), "");
+    }
+
+    #[test]
+    fn gecos_round_trips_middle_empty_subfield() {
+        const LINE: &str = "Joe Blow,,101";
+        let gecos = Gecos::parse(LINE);
+        assert_eq!(gecos.room_number, "");
+        assert_eq!(gecos.work_phone, "101");
+        assert_eq!(gecos.format(), LINE);
+    }
 }
@@ -0,0 +1,123 @@
+/// The `password` field shared by passwd(5) and shadow(5) entries.
+///
+/// This field is usually just a placeholder (`x` when the real hash lives in
+/// `/etc/shadow`, `*`/`!` when the account cannot be logged into directly,
+/// or empty for no password), but it can also hold a real crypt(3) hash.
+///
+/// Serializes as its plain textual field form (e.g. `"x"`, `"!"`, or the
+/// hash string) rather than a tagged enum, so JSON dumps read the same way
+/// the NSS file would.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Password {
+    /// `x` - the real password lives in `/etc/shadow`.
+    Shadowed,
+    /// `*` - the account is disabled.
+    Disabled,
+    /// `!` - the account is locked.
+    Locked,
+    /// The empty string - no password is required.
+    Empty,
+    /// A crypt(3)-style password hash.
+    Hash(String),
+}
+
+impl Password {
+    /// Parses the raw field text, preserving its exact textual form on
+    /// round-trip via [`Password::to_field`].
+    pub fn from_field(field: &str) -> Password {
+        match field {
+            "x" => Password::Shadowed,
+            "*" => Password::Disabled,
+            "!" => Password::Locked,
+            "" => Password::Empty,
+            hash => Password::Hash(hash.to_owned()),
+        }
+    }
+
+    pub fn to_field(&self) -> String {
+        match self {
+            Password::Shadowed => "x".to_owned(),
+            Password::Disabled => "*".to_owned(),
+            Password::Locked => "!".to_owned(),
+            Password::Empty => String::new(),
+            Password::Hash(hash) => hash.clone(),
+        }
+    }
+
+    /// True if the account cannot be logged into via password authentication.
+    pub fn is_locked(&self) -> bool {
+        matches!(self, Password::Disabled | Password::Locked)
+    }
+}
+
+impl serde::Serialize for Password {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_field())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Password {
+    fn deserialize<D>(deserializer: D) -> Result<Password, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let field = String::deserialize(deserializer)?;
+        Ok(Password::from_field(&field))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_field_recognizes_special_values() {
+        assert_eq!(Password::from_field("x"), Password::Shadowed);
+        assert_eq!(Password::from_field("*"), Password::Disabled);
+        assert_eq!(Password::from_field("!"), Password::Locked);
+        assert_eq!(Password::from_field(""), Password::Empty);
+    }
+
+    #[test]
+    fn from_field_keeps_hash() {
+        let hash = "$6$salt$abcdef";
+        assert_eq!(Password::from_field(hash), Password::Hash(hash.to_owned()));
+    }
+
+    #[test]
+    fn to_field_round_trips() {
+        for field in ["x", "*", "!", "", "$6$salt$abcdef"] {
+            assert_eq!(Password::from_field(field).to_field(), field);
+        }
+    }
+
+    #[test]
+    fn is_locked_works() {
+        assert!(Password::Disabled.is_locked());
+        assert!(Password::Locked.is_locked());
+        assert!(!Password::Shadowed.is_locked());
+        assert!(!Password::Empty.is_locked());
+        assert!(!Password::Hash("abc".to_owned()).is_locked());
+    }
+
+    #[test]
+    fn serializes_as_plain_field_string() {
+        assert_eq!(serde_json::to_string(&Password::Shadowed).unwrap(), "\"x\"");
+        assert_eq!(
+            serde_json::to_string(&Password::Hash("abc".to_owned())).unwrap(),
+            "\"abc\""
+        );
+    }
+
+    #[test]
+    fn json_round_trips() {
+        for field in ["x", "*", "!", "", "$6$salt$abcdef"] {
+            let pw = Password::from_field(field);
+            let json = serde_json::to_string(&pw).unwrap();
+            assert_eq!(serde_json::from_str::<Password>(&json).unwrap(), pw);
+        }
+    }
+}
@@ -1,17 +1,22 @@
 use crate::entfiles::{EntFileReader, EntFileWriter, EntLineParser, Entry, ReadEntryError};
+use crate::password::Password;
+use crate::sha512crypt;
 use crate::util::to_string_or_empty;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ShadowEntry {
     pub name: String,
-    pub passwd: String,
+    pub passwd: Password,
     pub last_change_date: Option<u32>,
     pub min_password_age: Option<u32>,
     pub max_password_age: Option<u32>,
     pub warn_period: Option<u32>,
     pub inact_period: Option<u32>,
     pub expire_date: Option<u32>,
-    // reserved
+    /// The reserved 9th field, and anything beyond it, preserved verbatim
+    /// across a read/write round-trip. `None` if the line had no such
+    /// columns at all.
+    pub raw_tail: Option<String>,
 }
 
 pub type ShadowFileReader = EntFileReader<ShadowEntry>;
@@ -23,29 +28,136 @@ impl Entry for ShadowEntry {
         let mut parser = EntLineParser::new(line);
         Ok(ShadowEntry {
             name: parser.next_field_string()?,
-            passwd: parser.next_field_string()?,
+            passwd: Password::from_field(parser.next_field_str()?),
             last_change_date: parser.next_field_u32_opt()?,
             min_password_age: parser.next_field_u32_opt()?,
             max_password_age: parser.next_field_u32_opt()?,
             warn_period: parser.next_field_u32_opt()?,
             inact_period: parser.next_field_u32_opt()?,
             expire_date: parser.next_field_u32_opt()?,
-            // 9th field unused
+            raw_tail: parser.remaining_str(),
         })
     }
 
     fn to_line(&self) -> String {
-        format!(
-            "{}:{}:{}:{}:{}:{}:{}:{}:",
+        let mut line = format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}",
             self.name,
-            self.passwd,
+            self.passwd.to_field(),
             to_string_or_empty(self.last_change_date),
             to_string_or_empty(self.min_password_age),
             to_string_or_empty(self.max_password_age),
             to_string_or_empty(self.warn_period),
             to_string_or_empty(self.inact_period),
             to_string_or_empty(self.expire_date),
-        )
+        );
+        if let Some(raw_tail) = &self.raw_tail {
+            line.push(':');
+            line.push_str(raw_tail);
+        }
+        line
+    }
+
+    fn key(&self) -> &str {
+        &self.name
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// AgingStatus
+
+/// The result of evaluating a [`ShadowEntry`]'s password/account aging
+/// fields against a given day, per shadow(5) semantics.
+#[derive(Debug, Eq, PartialEq)]
+pub struct AgingStatus {
+    /// The password is expired and must be changed before login, either
+    /// because `last_change_date` is `0` (forced change) or because
+    /// `today >= last_change_date + max_password_age`.
+    pub must_change_password: bool,
+    /// The password isn't expired yet, but is within `warn_period` days of
+    /// `last_change_date + max_password_age`.
+    pub in_warning_period: bool,
+    /// The account is locked because the password has been expired for
+    /// at least `inact_period` days
+    /// (`today >= last_change_date + max_password_age + inact_period`).
+    pub account_locked_due_to_inactivity: bool,
+    /// `today >= expire_date`, irrespective of password aging.
+    pub expired: bool,
+    /// The number of days until the next aging transition above, or `None`
+    /// if no transition is scheduled (e.g. no `max_password_age` or
+    /// `expire_date` is set).
+    pub days_until_next_transition: Option<u32>,
+}
+
+impl ShadowEntry {
+    /// Evaluates this entry's password/account aging fields as of `today`
+    /// (a day count since the epoch, matching this entry's own fields).
+    pub fn aging_status(&self, today: u32) -> AgingStatus {
+        // A last-change date of 0 forces a password change regardless of
+        // max_password_age (shadow(5)).
+        let forced_change = self.last_change_date == Some(0);
+
+        let max_age_boundary = self
+            .last_change_date
+            .zip(self.max_password_age)
+            .map(|(last_change, max_age)| last_change + max_age);
+
+        let must_change_password =
+            forced_change || max_age_boundary.is_some_and(|boundary| today >= boundary);
+
+        let warn_start = max_age_boundary
+            .zip(self.warn_period)
+            .map(|(boundary, warn)| boundary.saturating_sub(warn));
+        let in_warning_period = match (warn_start, max_age_boundary) {
+            (Some(warn_start), Some(boundary)) => today >= warn_start && today < boundary,
+            _ => false,
+        };
+
+        let lock_boundary = max_age_boundary
+            .zip(self.inact_period)
+            .map(|(boundary, inact)| boundary + inact);
+        let account_locked_due_to_inactivity =
+            lock_boundary.is_some_and(|boundary| today >= boundary);
+
+        let expired = self.expire_date.is_some_and(|expire| today >= expire);
+
+        let days_until_next_transition =
+            [max_age_boundary, warn_start, lock_boundary, self.expire_date]
+                .into_iter()
+                .flatten()
+                .filter(|boundary| *boundary > today)
+                .map(|boundary| boundary - today)
+                .min();
+
+        AgingStatus {
+            must_change_password,
+            in_warning_period,
+            account_locked_due_to_inactivity,
+            expired,
+            days_until_next_transition,
+        }
+    }
+
+    /// Hashes `plaintext` with SHA-512-crypt (`$6$`) and stores it as this
+    /// entry's password. `salt` defaults to a freshly generated 16-character
+    /// salt when `None`; `rounds` defaults to 5000 when `None`.
+    pub fn set_password(&mut self, plaintext: &str, salt: Option<&str>, rounds: Option<u32>) {
+        let salt = match salt {
+            Some(salt) => salt.to_owned(),
+            None => sha512crypt::generate_salt(),
+        };
+        let hashed = sha512crypt::hash(plaintext.as_bytes(), &salt, rounds);
+        self.passwd = Password::Hash(hashed);
+    }
+
+    /// Returns whether `plaintext` matches this entry's stored password
+    /// hash. Always `false` for special password states such as
+    /// [`Password::Locked`] or [`Password::Empty`].
+    pub fn verify_password(&self, plaintext: &str) -> bool {
+        match &self.passwd {
+            Password::Hash(stored) => sha512crypt::verify(plaintext.as_bytes(), stored),
+            _ => false,
+        }
     }
 }
 
@@ -59,13 +171,14 @@ mod tests {
     fn get_sample_ent() -> ShadowEntry {
         ShadowEntry {
             name: "joe".to_string(),
-            passwd: "*".to_string(),
+            passwd: Password::Disabled,
             last_change_date: Some(18881),
             min_password_age: Some(0),
             max_password_age: Some(99999),
             warn_period: Some(7),
             inact_period: None,
             expire_date: None,
+            raw_tail: Some(String::new()),
         }
     }
 
@@ -83,6 +196,15 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn raw_tail_round_trips_unmodeled_columns() -> Result<(), String> {
+        const LINE: &str = "joe:*:18881:0:99999:7:::future-use";
+        let ent = ShadowEntry::from_line(LINE).unwrap();
+        assert_eq!(ent.raw_tail, Some("future-use".to_string()));
+        assert_eq!(ent.to_line(), LINE);
+        Ok(())
+    }
+
     #[test]
     fn invalid_integer_none() -> Result<(), String> {
         let line = "joe:*:18881:0:9999x:7:::";
@@ -98,4 +220,112 @@ mod tests {
         assert_eq!(line, SAMPLE_LINE);
         Ok(())
     }
+
+    #[test]
+    fn set_password_then_verify_password_roundtrips() {
+        let mut ent = get_sample_ent();
+        ent.set_password("hunter2", Some("saltstring"), None);
+        assert_eq!(
+            ent.passwd,
+            Password::Hash(sha512crypt::hash(b"hunter2", "saltstring", None))
+        );
+        assert!(ent.verify_password("hunter2"));
+        assert!(!ent.verify_password("wrong"));
+    }
+
+    #[test]
+    fn set_password_generates_salt_when_none_given() {
+        let mut ent = get_sample_ent();
+        ent.set_password("hunter2", None, None);
+        assert!(ent.verify_password("hunter2"));
+    }
+
+    #[test]
+    fn verify_password_is_false_for_non_hash_states() {
+        let ent = get_sample_ent();
+        assert_eq!(ent.passwd, Password::Disabled);
+        assert!(!ent.verify_password("anything"));
+    }
+
+    fn aging_ent() -> ShadowEntry {
+        ShadowEntry {
+            name: "joe".to_string(),
+            passwd: Password::Hash("$6$salt$abc".to_string()),
+            last_change_date: Some(100),
+            min_password_age: Some(0),
+            max_password_age: Some(30),
+            warn_period: Some(7),
+            inact_period: Some(10),
+            expire_date: None,
+            raw_tail: None,
+        }
+    }
+
+    #[test]
+    fn aging_status_before_warning_window() {
+        let status = aging_ent().aging_status(100);
+        assert_eq!(
+            status,
+            AgingStatus {
+                must_change_password: false,
+                in_warning_period: false,
+                account_locked_due_to_inactivity: false,
+                expired: false,
+                days_until_next_transition: Some(23), // warning starts at day 123
+            }
+        );
+    }
+
+    #[test]
+    fn aging_status_within_warning_window() {
+        let status = aging_ent().aging_status(125);
+        assert!(!status.must_change_password);
+        assert!(status.in_warning_period);
+        assert_eq!(status.days_until_next_transition, Some(5)); // max age at day 130
+    }
+
+    #[test]
+    fn aging_status_must_change_password_after_max_age() {
+        let status = aging_ent().aging_status(130);
+        assert!(status.must_change_password);
+        assert!(!status.in_warning_period);
+        assert!(!status.account_locked_due_to_inactivity);
+        assert_eq!(status.days_until_next_transition, Some(10)); // locked at day 140
+    }
+
+    #[test]
+    fn aging_status_locked_after_inactivity_period() {
+        let status = aging_ent().aging_status(140);
+        assert!(status.must_change_password);
+        assert!(status.account_locked_due_to_inactivity);
+    }
+
+    #[test]
+    fn aging_status_forced_change_when_last_change_is_zero() {
+        let mut ent = aging_ent();
+        ent.last_change_date = Some(0);
+        let status = ent.aging_status(0);
+        assert!(status.must_change_password);
+    }
+
+    #[test]
+    fn aging_status_expired_overrides_password_aging() {
+        let mut ent = aging_ent();
+        ent.expire_date = Some(50);
+        let status = ent.aging_status(60);
+        assert!(status.expired);
+    }
+
+    #[test]
+    fn aging_status_never_expires_without_max_age() {
+        let ent = ShadowEntry {
+            max_password_age: None,
+            ..aging_ent()
+        };
+        let status = ent.aging_status(10_000);
+        assert!(!status.must_change_password);
+        assert!(!status.in_warning_period);
+        assert!(!status.account_locked_due_to_inactivity);
+        assert_eq!(status.days_until_next_transition, None);
+    }
 }
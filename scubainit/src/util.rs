@@ -2,6 +2,7 @@ use anyhow::{Context as _, Result};
 use std::env;
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
 /// Opens a file for reading and appending.
@@ -15,6 +16,44 @@ pub fn open_read_append<P: AsRef<Path>>(path: P) -> std::io::Result<fs::File> {
         .open(path)
 }
 
+/// Holds an exclusive advisory lock (`flock(2)`) on an ent-file for the
+/// duration of a read-scan-append transaction, releasing it when dropped.
+///
+/// Guards against two processes (e.g. a root hook and scubainit, or two
+/// scubainit instances sharing a volume) racing on the same unlocked
+/// read-then-append.
+pub struct FileLock {
+    file: fs::File,
+}
+
+impl FileLock {
+    /// Opens `path` for reading and appending (see [`open_read_append`])
+    /// and takes an exclusive lock on it, blocking until it's available.
+    pub fn open_read_append<P: AsRef<Path>>(path: P) -> std::io::Result<FileLock> {
+        let file = open_read_append(path)?;
+        // SAFETY: flock() only touches the given file descriptor.
+        unsafe {
+            libc_result(libc::flock(file.as_raw_fd(), libc::LOCK_EX))?;
+        }
+        Ok(FileLock { file })
+    }
+
+    /// Returns a duplicate handle to the locked file, e.g. to hand off to
+    /// an [`crate::entfiles::EntFileReader`]/[`crate::entfiles::EntFileWriter`]
+    /// pair. The lock stays held by this guard regardless of what happens
+    /// to the duplicate.
+    pub fn try_clone(&self) -> std::io::Result<fs::File> {
+        self.file.try_clone()
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // SAFETY: flock() only touches the given file descriptor.
+        let _ = unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
 pub fn short_write() -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::Other, "Short write")
 }
@@ -75,6 +114,16 @@ pub fn pop_env_uint(name: &str) -> Result<Option<u32>> {
     Ok(Some(value))
 }
 
+/// Returns the number of whole days elapsed since the Unix epoch, as stored
+/// in the shadow(5) `sp_lstchg` field.
+pub fn today_days_since_epoch() -> u32 {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    (secs / 86400) as u32
+}
+
 pub fn make_executable(path: &str) -> std::io::Result<()> {
     let mut perms = fs::metadata(path)?.permissions();
     let mut mode = perms.mode();
@@ -177,4 +226,26 @@ mod tests {
             assert!(not_set(VAR_NAME));
         });
     }
+
+    #[test]
+    fn file_lock_try_clone_shares_the_locked_file() -> std::io::Result<()> {
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("scubainit-file-lock-test-{}", std::process::id()));
+        fs::write(&path, b"hello")?;
+
+        let lock = FileLock::open_read_append(&path)?;
+        let mut clone = lock.try_clone()?;
+        clone.write_all(b" world")?;
+
+        let mut contents = String::new();
+        clone.seek(SeekFrom::Start(0))?;
+        clone.read_to_string(&mut contents)?;
+        assert_eq!(contents, "hello world");
+
+        drop(lock);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
 }
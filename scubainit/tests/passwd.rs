@@ -3,7 +3,8 @@ use std::fs;
 use std::io::{BufRead, BufReader};
 use std::io::{Read, Seek, Write};
 
-use scubainit::passwd::{PasswdEntry, PasswdFileReader, PasswdFileWriter};
+use scubainit::password::Password;
+use scubainit::passwd::{Gecos, PasswdEntry, PasswdFileReader, PasswdFileWriter};
 use scubainit::util::open_read_append;
 
 const SAMPLE_LINE: &str = "shemp:x:1003:1003:Shemp Howard:/home/shemp:/bin/fish";
@@ -11,10 +12,13 @@ const SAMPLE_LINE: &str = "shemp:x:1003:1003:Shemp Howard:/home/shemp:/bin/fish"
 fn get_sample_ent() -> PasswdEntry {
     PasswdEntry {
         name: "shemp".to_string(),
-        passwd: "x".to_string(),
+        passwd: Password::Shadowed,
         uid: 1003,
         gid: 1003,
-        gecos: "Shemp Howard".to_string(),
+        gecos: Gecos {
+            full_name: "Shemp Howard".to_string(),
+            ..Default::default()
+        },
         home_dir: "/home/shemp".to_string(),
         shell: "/bin/fish".to_string(),
     }
@@ -35,28 +39,28 @@ fn test_passwd1() -> Result<()> {
 
     let pw = reader.next().unwrap()?;
     assert_eq!(pw.name, "moe");
-    assert_eq!(pw.passwd, "x");
+    assert_eq!(pw.passwd, Password::Shadowed);
     assert_eq!(pw.uid, 1001);
     assert_eq!(pw.gid, 1001);
-    assert_eq!(pw.gecos, "Moe Howard");
+    assert_eq!(pw.gecos.full_name, "Moe Howard");
     assert_eq!(pw.home_dir, "/home/moe");
     assert_eq!(pw.shell, "/bin/zsh");
 
     let pw = reader.next().unwrap()?;
     assert_eq!(pw.name, "larry");
-    assert_eq!(pw.passwd, "x");
+    assert_eq!(pw.passwd, Password::Shadowed);
     assert_eq!(pw.uid, 1002);
     assert_eq!(pw.gid, 1002);
-    assert_eq!(pw.gecos, "Larry Fine");
+    assert_eq!(pw.gecos.full_name, "Larry Fine");
     assert_eq!(pw.home_dir, "/home/larry");
     assert_eq!(pw.shell, "/bin/ksh");
 
     let pw = reader.next().unwrap()?;
     assert_eq!(pw.name, "shemp");
-    assert_eq!(pw.passwd, "x");
+    assert_eq!(pw.passwd, Password::Shadowed);
     assert_eq!(pw.uid, 1003);
     assert_eq!(pw.gid, 1003);
-    assert_eq!(pw.gecos, "Shemp Howard");
+    assert_eq!(pw.gecos.full_name, "Shemp Howard");
     assert_eq!(pw.home_dir, "/home/shemp");
     assert_eq!(pw.shell, "/bin/fish");
 
@@ -3,6 +3,7 @@ use std::fs;
 use std::io::{BufRead, BufReader};
 use std::io::{Read, Seek, Write};
 
+use scubainit::password::Password;
 use scubainit::shadow::{ShadowEntry, ShadowFileReader, ShadowFileWriter};
 use scubainit::util::open_read_append;
 
@@ -11,13 +12,14 @@ const SAMPLE_LINE: &str = "joe:!:18881:0:99999:7::19876:";
 fn get_sample_ent() -> ShadowEntry {
     ShadowEntry {
         name: "joe".to_string(),
-        passwd: "!".to_string(),
+        passwd: Password::Locked,
         last_change_date: Some(18881),
         min_password_age: Some(0),
         max_password_age: Some(99999),
         warn_period: Some(7),
         inact_period: None,
         expire_date: Some(19876),
+        raw_tail: Some(String::new()),
     }
 }
 
@@ -37,7 +39,7 @@ fn test_shadow1() -> Result<()> {
     // root:$y$j9T$zzzzzzzzzzzzzzz:18881:0:99999:7:::
     let result = reader.next().unwrap()?;
     assert_eq!(result.name, "root");
-    assert_eq!(result.passwd, "$y$j9T$zzzzzzzzzzzzzzz");
+    assert_eq!(result.passwd, Password::Hash("$y$j9T$zzzzzzzzzzzzzzz".to_string()));
     assert_eq!(result.last_change_date, Some(18881));
     assert_eq!(result.min_password_age, Some(0));
     assert_eq!(result.max_password_age, Some(99999));
@@ -48,7 +50,7 @@ fn test_shadow1() -> Result<()> {
     // systemd-timesync:*:18881:0:99999:7:::
     let result = reader.next().unwrap()?;
     assert_eq!(result.name, "systemd-timesync");
-    assert_eq!(result.passwd, "*");
+    assert_eq!(result.passwd, Password::Disabled);
     assert_eq!(result.last_change_date, Some(18881));
     assert_eq!(result.min_password_age, Some(0));
     assert_eq!(result.max_password_age, Some(99999));